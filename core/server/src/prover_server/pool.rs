@@ -1,10 +1,13 @@
 // Built-in
 use std::collections::{HashMap, VecDeque};
-use std::sync::{Arc, RwLock};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
 use std::{thread, time};
 // External
 use crate::franklin_crypto::bellman::pairing::ff::PrimeField;
-use log::info;
+use crossbeam::thread as crossbeam_thread;
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
 // Workspace deps
 use circuit::witness::change_pubkey_offchain::{
     apply_change_pubkey_offchain_tx, calculate_change_pubkey_offchain_from_witness,
@@ -52,7 +55,9 @@ impl BlockSizedOperationsQueue {
         limit: i64,
     ) -> Result<(), String> {
         if self.operations.len() < limit as usize {
-            let storage = conn_pool.access_storage().expect("failed to connect to db");
+            let storage = conn_pool
+                .access_storage()
+                .map_err(|e| format!("failed to connect to db: {}", e))?;
             let ops = storage
                 .load_unverified_commits_after_block(self.block_size, self.last_loaded_block, limit)
                 .map_err(|e| format!("failed to read commit operations: {}", e))?;
@@ -82,7 +87,9 @@ impl BlockSizedOperationsQueue {
     ) -> Result<Option<(BlockNumber, ProverData)>, String> {
         match self.operations.pop_front() {
             Some(op) => {
-                let storage = conn_pool.access_storage().expect("failed to connect to db");
+                let storage = conn_pool
+                    .access_storage()
+                    .map_err(|e| format!("failed to connect to db: {}", e))?;
                 let pd = build_prover_data(&storage, &op)?;
                 Ok(Some((op.block.block_number, pd)))
             }
@@ -91,18 +98,264 @@ impl BlockSizedOperationsQueue {
     }
 }
 
+// How a `ProverData` entry is physically stored in the `prepared` cache.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ProverDataEncoding {
+    Raw,
+    Zstd,
+    Base64Zstd,
+}
+
+// A `ProverData`, bincode-serialized and optionally zstd/base64 encoded.
+#[derive(Clone)]
+struct StoredProverData {
+    encoding: ProverDataEncoding,
+    bytes: Vec<u8>,
+}
+
+// Compresses `raw` per `encoding`, falling back to `Raw` if the payload that would be stored
+// (the base64 text for `Base64Zstd`) is not smaller than `raw`, or if zstd errors.
+// Pulled out of `StoredProverData::encode` so the codec itself is testable without a `ProverData`.
+fn compress_bytes(
+    block: BlockNumber,
+    raw: Vec<u8>,
+    encoding: ProverDataEncoding,
+    level: i32,
+) -> (ProverDataEncoding, Vec<u8>) {
+    if encoding == ProverDataEncoding::Raw {
+        return (ProverDataEncoding::Raw, raw);
+    }
+
+    match zstd::stream::encode_all(&raw[..], level) {
+        Ok(compressed) => {
+            // For Base64Zstd the stored/transmitted payload is the base64 text, not the raw
+            // zstd output, so the shrink check has to run on what's actually kept.
+            let stored = if encoding == ProverDataEncoding::Base64Zstd {
+                base64::encode(&compressed).into_bytes()
+            } else {
+                compressed
+            };
+
+            if stored.len() < raw.len() {
+                let ratio = raw.len() as f64 / stored.len() as f64;
+                info!(
+                    "compressed prover data for block {}: {} -> {} bytes ({:.2}x, {})",
+                    block,
+                    raw.len(),
+                    stored.len(),
+                    ratio,
+                    if encoding == ProverDataEncoding::Base64Zstd {
+                        "zstd+base64"
+                    } else {
+                        "zstd"
+                    }
+                );
+                (encoding, stored)
+            } else {
+                warn!(
+                    "compression did not shrink prover data for block {}, storing raw",
+                    block
+                );
+                (ProverDataEncoding::Raw, raw)
+            }
+        }
+        Err(err) => {
+            warn!(
+                "failed to zstd-compress prover data for block {}: {}, storing raw",
+                block, err
+            );
+            (ProverDataEncoding::Raw, raw)
+        }
+    }
+}
+
+// Inverse of `compress_bytes`.
+fn decompress_bytes(encoding: ProverDataEncoding, bytes: &[u8]) -> Result<Vec<u8>, String> {
+    match encoding {
+        ProverDataEncoding::Raw => Ok(bytes.to_vec()),
+        ProverDataEncoding::Zstd => zstd::stream::decode_all(bytes)
+            .map_err(|e| format!("failed to decompress prover data: {}", e)),
+        ProverDataEncoding::Base64Zstd => {
+            let compressed = base64::decode(bytes)
+                .map_err(|e| format!("failed to base64-decode prover data: {}", e))?;
+            zstd::stream::decode_all(&compressed[..])
+                .map_err(|e| format!("failed to decompress prover data: {}", e))
+        }
+    }
+}
+
+impl StoredProverData {
+    fn encode(
+        block: BlockNumber,
+        data: &ProverData,
+        encoding: ProverDataEncoding,
+        level: i32,
+    ) -> Self {
+        let raw = bincode::serialize(data).expect("failed to serialize prover data");
+        let (encoding, bytes) = compress_bytes(block, raw, encoding, level);
+        Self { encoding, bytes }
+    }
+
+    fn decode(&self) -> Result<ProverData, String> {
+        let raw = decompress_bytes(self.encoding, &self.bytes)?;
+        bincode::deserialize(&raw).map_err(|e| format!("failed to deserialize prover data: {}", e))
+    }
+}
+
+// LRU-bounded cache of finished `ProverData`, keyed by the block it was built for.
+struct PreparedDataCacheState {
+    entries: HashMap<BlockNumber, StoredProverData>,
+    recency: VecDeque<BlockNumber>,
+    // Rounds each unserved block has survived without being fetched; absence means served.
+    unserved: HashMap<BlockNumber, u32>,
+    capacity: usize,
+    max_unserved_rounds: u32,
+}
+
+impl PreparedDataCacheState {
+    fn touch(&mut self, block: BlockNumber) {
+        if let Some(pos) = self.recency.iter().position(|b| *b == block) {
+            self.recency.remove(pos);
+            self.recency.push_back(block);
+        }
+    }
+
+    fn evict_if_over_capacity(&mut self) {
+        while self.entries.len() > self.capacity {
+            let evictable = self.recency.iter().position(|block| {
+                self.unserved
+                    .get(block)
+                    .map_or(true, |age| *age >= self.max_unserved_rounds)
+            });
+
+            match evictable {
+                Some(pos) => {
+                    let block = self.recency.remove(pos).expect("checked position");
+                    self.entries.remove(&block);
+                    if self.unserved.remove(&block).is_some() {
+                        warn!(
+                            "evicting block {} from prepared prover-data cache before it was ever served (exceeded {}-round unserved grace period)",
+                            block, self.max_unserved_rounds
+                        );
+                    }
+                }
+                None => {
+                    warn!(
+                        "prepared prover-data cache holds {} blocks (capacity {}) but all of them are still within their {}-round unserved grace period",
+                        self.entries.len(),
+                        self.capacity,
+                        self.max_unserved_rounds
+                    );
+                    break;
+                }
+            }
+        }
+    }
+}
+
+// Bookkeeping lives behind its own mutex so `get` never needs the pool's write lock.
+struct PreparedDataCache {
+    state: Mutex<PreparedDataCacheState>,
+    encoding: ProverDataEncoding,
+    compression_level: i32,
+}
+
+impl PreparedDataCache {
+    fn new(
+        capacity: usize,
+        encoding: ProverDataEncoding,
+        compression_level: i32,
+        max_unserved_rounds: u32,
+    ) -> Self {
+        Self {
+            state: Mutex::new(PreparedDataCacheState {
+                entries: HashMap::new(),
+                recency: VecDeque::new(),
+                unserved: HashMap::new(),
+                capacity,
+                max_unserved_rounds,
+            }),
+            encoding,
+            compression_level,
+        }
+    }
+
+    // Ages unserved entries by one round; called once per `maintain` round.
+    fn begin_round(&self) {
+        let mut state = self.state.lock().expect("prepared cache lock poisoned");
+        for age in state.unserved.values_mut() {
+            *age = age.saturating_add(1);
+        }
+    }
+
+    fn insert(&self, block: BlockNumber, data: ProverData) {
+        let stored = StoredProverData::encode(block, &data, self.encoding, self.compression_level);
+        self.insert_stored(block, stored);
+    }
+
+    // Shared by `insert` and, directly, by tests that don't need a real `ProverData`.
+    fn insert_stored(&self, block: BlockNumber, stored: StoredProverData) {
+        let mut state = self.state.lock().expect("prepared cache lock poisoned");
+        if state.entries.insert(block, stored).is_none() {
+            state.recency.push_back(block);
+        }
+        state.unserved.insert(block, 0);
+        state.evict_if_over_capacity();
+    }
+
+    fn get(&self, block: BlockNumber) -> Result<Option<ProverData>, String> {
+        let stored = {
+            let mut state = self.state.lock().expect("prepared cache lock poisoned");
+            if state.entries.contains_key(&block) {
+                state.unserved.remove(&block);
+                state.touch(block);
+            }
+            state.entries.get(&block).cloned()
+        };
+
+        stored.map(|stored| stored.decode()).transpose()
+    }
+
+    fn remove(&self, block: BlockNumber) {
+        let mut state = self.state.lock().expect("prepared cache lock poisoned");
+        state.entries.remove(&block);
+        state.unserved.remove(&block);
+        if let Some(pos) = state.recency.iter().position(|b| *b == block) {
+            state.recency.remove(pos);
+        }
+    }
+}
+
 pub struct ProversDataPool {
     limit: i64,
     op_queues: HashMap<usize, BlockSizedOperationsQueue>,
-    prepared: HashMap<BlockNumber, ProverData>,
+    prepared: PreparedDataCache,
+    // Upper bound on concurrent queue builds in `prepare_next`, capped by `db_pool_size`.
+    workers: usize,
+    db_pool_size: usize,
 }
 
 impl ProversDataPool {
-    pub fn new(limit: i64) -> Self {
+    pub fn new(
+        limit: i64,
+        prepared_capacity: usize,
+        prepared_encoding: ProverDataEncoding,
+        zstd_level: i32,
+        max_unserved_rounds: u32,
+        workers: usize,
+        db_pool_size: usize,
+    ) -> Self {
         let mut res = Self {
             limit,
             op_queues: HashMap::new(),
-            prepared: HashMap::new(),
+            prepared: PreparedDataCache::new(
+                prepared_capacity,
+                prepared_encoding,
+                zstd_level,
+                max_unserved_rounds,
+            ),
+            workers,
+            db_pool_size,
         };
 
         for block_size in models::params::block_chunk_sizes() {
@@ -113,12 +366,12 @@ impl ProversDataPool {
         res
     }
 
-    pub fn get(&self, block: BlockNumber) -> Option<&ProverData> {
-        self.prepared.get(&block)
+    pub fn get(&self, block: BlockNumber) -> Result<Option<ProverData>, String> {
+        self.prepared.get(block)
     }
 
-    pub fn clean_up(&mut self, block: BlockNumber) {
-        self.prepared.remove(&block);
+    pub fn clean_up(&self, block: BlockNumber) {
+        self.prepared.remove(block);
     }
 
     fn take_next_commits_if_needed(
@@ -132,30 +385,138 @@ impl ProversDataPool {
         Ok(())
     }
 
+    // Builds witnesses per block size concurrently; the replay inside `build_prover_data`
+    // itself stays sequential since it mutates a single `CircuitAccountTree`.
     fn prepare_next(&mut self, conn_pool: &storage::ConnectionPool) -> Result<(), String> {
-        for (_, queue) in self.op_queues.iter_mut() {
-            if let Some((block_number, pd)) = queue.prepare_next_if_any(conn_pool)? {
-                self.prepared.insert(block_number, pd);
+        self.prepared.begin_round();
+
+        let worker_limit = self.workers.min(self.db_pool_size).max(1);
+        let mut queues: Vec<&mut BlockSizedOperationsQueue> = self.op_queues.values_mut().collect();
+        let mut prepared_blocks = Vec::new();
+
+        for batch in queues.chunks_mut(worker_limit) {
+            let batch_results = crossbeam_thread::scope(|scope| {
+                let handles: Vec<_> = batch
+                    .iter_mut()
+                    .map(|queue| scope.spawn(move |_| queue.prepare_next_if_any(conn_pool)))
+                    .collect();
+
+                handles
+                    .into_iter()
+                    .map(|handle| {
+                        handle.join().unwrap_or_else(|payload| {
+                            Err(format!(
+                                "prover-data preparation worker thread panicked: {}",
+                                describe_panic(&payload)
+                            ))
+                        })
+                    })
+                    .collect::<Vec<_>>()
+            })
+            .map_err(|payload| {
+                format!(
+                    "prover-data preparation worker scope panicked: {}",
+                    describe_panic(&payload)
+                )
+            })?;
+
+            for result in batch_results {
+                if let Some(pair) = result? {
+                    prepared_blocks.push(pair);
+                }
             }
         }
 
+        for (block_number, pd) in prepared_blocks {
+            self.prepared.insert(block_number, pd);
+        }
+
         Ok(())
     }
 }
 
+// Extracts a human-readable message from a caught `std::panic` payload.
+fn describe_panic(payload: &(dyn std::any::Any + Send)) -> String {
+    if let Some(s) = payload.downcast_ref::<&str>() {
+        (*s).to_string()
+    } else if let Some(s) = payload.downcast_ref::<String>() {
+        s.clone()
+    } else {
+        "unknown panic payload".to_string()
+    }
+}
+
+// Runs one round of commit-loading and witness preparation under the pool's write lock.
+fn run_maintain_round(
+    conn_pool: &storage::ConnectionPool,
+    data: &Arc<RwLock<ProversDataPool>>,
+) -> Result<(), String> {
+    let mut pool = data
+        .write()
+        .map_err(|e| format!("failed to get write lock on prover data pool: {}", e))?;
+    pool.take_next_commits_if_needed(conn_pool)?;
+    pool.prepare_next(conn_pool)?;
+    Ok(())
+}
+
+// Drives the prover-data preparation loop until `shutdown` is set, retrying failed rounds
+// with exponential backoff and escalating after `max_consecutive_failures` in a row.
 pub fn maintain(
     conn_pool: storage::ConnectionPool,
     data: Arc<RwLock<ProversDataPool>>,
     rounds_interval: time::Duration,
-) {
+    max_backoff_interval: time::Duration,
+    max_consecutive_failures: u32,
+    shutdown: Arc<AtomicBool>,
+) -> Result<(), String> {
     info!("preparing prover data routine started");
-    loop {
-        let mut pool = data.write().expect("failed to get write lock on data");
-        pool.take_next_commits_if_needed(&conn_pool)
-            .expect("couldn't get next commits");
-        pool.prepare_next(&conn_pool)
-            .expect("couldn't prepare next commits");
-        thread::sleep(rounds_interval);
+
+    let mut backoff = rounds_interval;
+    let mut consecutive_failures = 0u32;
+
+    while !shutdown.load(Ordering::SeqCst) {
+        match run_maintain_round(&conn_pool, &data) {
+            Ok(()) => {
+                backoff = rounds_interval;
+                consecutive_failures = 0;
+                sleep_interruptibly(rounds_interval, &shutdown);
+            }
+            Err(err) => {
+                consecutive_failures += 1;
+                warn!(
+                    "prover data maintenance round failed ({}/{} consecutive failures): {}",
+                    consecutive_failures, max_consecutive_failures, err
+                );
+
+                if consecutive_failures >= max_consecutive_failures {
+                    return Err(format!(
+                        "prover data maintenance failed {} times in a row, last error: {}",
+                        consecutive_failures, err
+                    ));
+                }
+
+                sleep_interruptibly(backoff, &shutdown);
+                backoff = std::cmp::min(backoff * 2, max_backoff_interval);
+            }
+        }
+    }
+
+    info!("prover data maintenance routine stopped on shutdown signal");
+    Ok(())
+}
+
+// Sleeps in slices, re-checking `shutdown` between each one instead of just once per loop.
+const SHUTDOWN_POLL_INTERVAL: time::Duration = time::Duration::from_millis(100);
+
+fn sleep_interruptibly(duration: time::Duration, shutdown: &AtomicBool) {
+    let mut remaining = duration;
+    while remaining > time::Duration::from_millis(0) {
+        if shutdown.load(Ordering::SeqCst) {
+            return;
+        }
+        let step = std::cmp::min(remaining, SHUTDOWN_POLL_INTERVAL);
+        thread::sleep(step);
+        remaining -= step;
     }
 }
 
@@ -410,3 +771,121 @@ fn build_prover_data(
         validator_account: witness_accum.fee_account_witness.unwrap(),
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn raw_encoding_roundtrips() {
+        let raw = b"some prover data payload".to_vec();
+        let (encoding, bytes) = compress_bytes(1, raw.clone(), ProverDataEncoding::Raw, 3);
+        assert_eq!(encoding, ProverDataEncoding::Raw);
+        assert_eq!(decompress_bytes(encoding, &bytes).unwrap(), raw);
+    }
+
+    #[test]
+    fn zstd_encoding_roundtrips() {
+        let raw = vec![0u8; 4096];
+        let (encoding, bytes) = compress_bytes(1, raw.clone(), ProverDataEncoding::Zstd, 3);
+        assert_eq!(encoding, ProverDataEncoding::Zstd);
+        assert!(bytes.len() < raw.len());
+        assert_eq!(decompress_bytes(encoding, &bytes).unwrap(), raw);
+    }
+
+    #[test]
+    fn base64_zstd_encoding_roundtrips() {
+        let raw = vec![7u8; 4096];
+        let (encoding, bytes) = compress_bytes(1, raw.clone(), ProverDataEncoding::Base64Zstd, 3);
+        assert_eq!(encoding, ProverDataEncoding::Base64Zstd);
+        assert_eq!(decompress_bytes(encoding, &bytes).unwrap(), raw);
+    }
+
+    #[test]
+    fn compression_falls_back_to_raw_when_it_does_not_shrink_payload() {
+        // Too small/incompressible for zstd to beat its own framing overhead.
+        let raw = vec![1u8, 2, 3];
+        let (encoding, bytes) = compress_bytes(1, raw.clone(), ProverDataEncoding::Zstd, 3);
+        assert_eq!(encoding, ProverDataEncoding::Raw);
+        assert_eq!(bytes, raw);
+    }
+
+    #[test]
+    fn base64_falls_back_to_raw_when_zstd_shrinks_but_base64_does_not() {
+        // Mostly incompressible payload with just enough redundancy (a run of zeros) that
+        // zstd shaves some bytes off, but not enough to survive the ~33% base64 blowup.
+        let mut raw = vec![0u8; 1024];
+        let mut state = 0x9E37_79B9u32;
+        raw.extend((0..7168).map(|_| {
+            // xorshift32; the top byte of each step gives a non-repeating byte stream.
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+            (state >> 24) as u8
+        }));
+
+        let (zstd_encoding, zstd_bytes) =
+            compress_bytes(1, raw.clone(), ProverDataEncoding::Zstd, 3);
+        assert_eq!(zstd_encoding, ProverDataEncoding::Zstd);
+        assert!(zstd_bytes.len() < raw.len());
+
+        let (base64_encoding, base64_bytes) =
+            compress_bytes(1, raw.clone(), ProverDataEncoding::Base64Zstd, 3);
+        assert_eq!(base64_encoding, ProverDataEncoding::Raw);
+        assert_eq!(base64_bytes, raw);
+    }
+
+    fn stub_entry() -> StoredProverData {
+        StoredProverData {
+            encoding: ProverDataEncoding::Raw,
+            bytes: Vec::new(),
+        }
+    }
+
+    fn contains(cache: &PreparedDataCache, block: BlockNumber) -> bool {
+        cache
+            .state
+            .lock()
+            .expect("prepared cache lock poisoned")
+            .entries
+            .contains_key(&block)
+    }
+
+    #[test]
+    fn unserved_entries_survive_eviction_pressure_within_their_grace_period() {
+        let cache = PreparedDataCache::new(1, ProverDataEncoding::Raw, 0, 2);
+
+        cache.insert_stored(1, stub_entry());
+        cache.insert_stored(2, stub_entry());
+
+        // Over capacity, but both blocks are unserved and within their grace period.
+        assert!(contains(&cache, 1));
+        assert!(contains(&cache, 2));
+    }
+
+    #[test]
+    fn unserved_entries_are_evicted_once_the_grace_period_expires() {
+        let cache = PreparedDataCache::new(1, ProverDataEncoding::Raw, 0, 1);
+
+        cache.insert_stored(1, stub_entry());
+        cache.begin_round(); // block 1 is now 1 round stale, at the grace-period limit
+        cache.insert_stored(2, stub_entry()); // triggers eviction with the pool over capacity
+
+        assert!(!contains(&cache, 1));
+        assert!(contains(&cache, 2));
+    }
+
+    #[test]
+    fn serving_an_entry_makes_it_immediately_evictable() {
+        let cache = PreparedDataCache::new(1, ProverDataEncoding::Raw, 0, 100);
+
+        cache.insert_stored(1, stub_entry());
+        // The empty stub bytes are not a valid `ProverData`, so decoding fails, but the
+        // lookup itself still marks block 1 as served.
+        assert!(cache.get(1).is_err());
+        cache.insert_stored(2, stub_entry());
+
+        assert!(!contains(&cache, 1));
+        assert!(contains(&cache, 2));
+    }
+}